@@ -65,12 +65,16 @@
 extern crate log;
 extern crate time;
 extern crate thread_local;
+extern crate atty;
 
-use log::{LogLevelFilter, LogMetadata};
+use log::{LogLevel, LogLevelFilter, LogMetadata};
 use std::cell::RefCell;
 use std::collections::BTreeSet;
 use std::collections::Bound;
+use std::env;
+use std::fmt;
 use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
 use thread_local::CachedThreadLocal;
 
 /// State of the timestampping in the logger.
@@ -80,15 +84,100 @@ pub enum Timestamp {
     Off,
     /// Timestamp with second granularity
     Second,
+    /// Timestamp with millisecond granularity
+    Millisecond,
+    /// Timestamp with microsecond granularity
+    Microsecond,
+    /// Timestamp with nanosecond granularity
+    Nanosecond,
+    /// Timestamp as the integer number of seconds since the Unix epoch
+    Epoch,
 }
 
-#[derive(Debug)]
+/// Coloring mode of the log-level token in each message.
+#[derive(Clone, Copy, Debug)]
+pub enum ColorChoice {
+    /// Colorize only when the output target is a terminal (stderr or
+    /// stdout; never for a `LogTarget::Pipe`) and `NO_COLOR` is unset
+    Auto,
+    /// Always colorize, even when piped to a file
+    Always,
+    /// Never colorize
+    Never,
+}
+
+/// Destination that log messages are written to.
+pub enum LogTarget {
+    /// Write to stderr (the default)
+    Stderr,
+    /// Write to stdout
+    Stdout,
+    /// Write to a caller-supplied sink, e.g. for capturing output in tests
+    Pipe(Arc<Mutex<Box<Write + Send>>>),
+}
+
+impl LogTarget {
+    /// Wraps an arbitrary `Write` implementation as a `LogTarget`
+    pub fn pipe<W: Write + Send + 'static>(writer: W) -> LogTarget {
+        LogTarget::Pipe(Arc::new(Mutex::new(Box::new(writer))))
+    }
+}
+
+impl Clone for LogTarget {
+    fn clone(&self) -> LogTarget {
+        match *self {
+            LogTarget::Stderr => LogTarget::Stderr,
+            LogTarget::Stdout => LogTarget::Stdout,
+            LogTarget::Pipe(ref sink) => LogTarget::Pipe(sink.clone()),
+        }
+    }
+}
+
+impl fmt::Debug for LogTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LogTarget::Stderr => write!(f, "Stderr"),
+            LogTarget::Stdout => write!(f, "Stdout"),
+            LogTarget::Pipe(_) => write!(f, "Pipe(..)"),
+        }
+    }
+}
+
+// adapts a shared sink so each thread's `LineWriter` can buffer its own
+// lines while still serializing the underlying writes through the lock
+struct SharedWriter(Arc<Mutex<Box<Write + Send>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+fn new_writer(target: &LogTarget) -> Box<Write + Send> {
+    match *target {
+        LogTarget::Stderr => Box::new(io::LineWriter::new(io::stderr())),
+        LogTarget::Stdout => Box::new(io::LineWriter::new(io::stdout())),
+        LogTarget::Pipe(ref sink) => Box::new(io::LineWriter::new(SharedWriter(sink.clone()))),
+    }
+}
+
+// the full line-rendering override set via `StdErrLog::format`
+type LogFormatFn = Fn(&mut Write, &log::LogRecord) -> io::Result<()> + Send + Sync;
+
 pub struct StdErrLog {
     verbosity: LogLevelFilter,
     quiet: bool,
     timestamp: Timestamp,
     modules: BTreeSet<String>,
-    writer: CachedThreadLocal<RefCell<io::LineWriter<io::Stderr>>>,
+    color: ColorChoice,
+    directives: Vec<(String, LogLevelFilter)>,
+    target: LogTarget,
+    format: Option<Arc<LogFormatFn>>,
+    writer: CachedThreadLocal<RefCell<Box<Write + Send>>>,
 }
 
 impl Clone for StdErrLog {
@@ -98,37 +187,76 @@ impl Clone for StdErrLog {
             quiet: self.quiet,
             timestamp: self.timestamp,
             modules: self.modules.clone(),
+            color: self.color,
+            directives: self.directives.clone(),
+            target: self.target.clone(),
+            format: self.format.clone(),
             writer: CachedThreadLocal::new(),
         }
     }
 }
 
+impl fmt::Debug for StdErrLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StdErrLog")
+            .field("verbosity", &self.verbosity)
+            .field("quiet", &self.quiet)
+            .field("timestamp", &self.timestamp)
+            .field("modules", &self.modules)
+            .field("color", &self.color)
+            .field("directives", &self.directives)
+            .field("target", &self.target)
+            .field("format", &self.format.is_some())
+            .finish()
+    }
+}
+
 impl log::Log for StdErrLog {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= self.log_level_filter()
+        // `LogMetadata` carries no module path, so this filters on
+        // `metadata.target()`. the target defaults to the module path but
+        // can be overridden by an explicit `target:` in a `log!` call; for
+        // such a record this check and the `module_path()` check in `log`
+        // below may pick different thresholds
+        metadata.level() <= self.level_for_module(metadata.target())
     }
 
     fn log(&self, record: &log::LogRecord) {
 
-        // if logging isn't enabled for this level do a quick out
-        if !self.enabled(record.metadata()) {
-            return;
-        }
-
         // module we are logging for
         let curr_mod = record.location().module_path();
 
+        // derive the per-module threshold from `module_path()` rather than
+        // deferring to `enabled()`, so the level check here and the module
+        // check below both go by the same string
+        if record.level() > self.level_for_module(curr_mod) {
+            return;
+        }
+
         // this logger only logs the requested modules unless the
         // vector of modules is empty
         // modules will have module::file in the module_path
         if self.includes_module(curr_mod) {
-            let writer =
-                self.writer.get_or(|| Box::new(RefCell::new(io::LineWriter::new(io::stderr()))));
+            let writer = self.writer.get_or(|| Box::new(RefCell::new(new_writer(&self.target))));
             let mut writer = writer.borrow_mut();
-            if let Timestamp::Second = self.timestamp {
-                let _ = write!(writer, "{} - ", time::now().rfc3339());
+
+            if let Some(ref format) = self.format {
+                let _ = format(&mut **writer, record);
+                return;
+            }
+
+            if let Some(timestamp) = format_timestamp(self.timestamp) {
+                let _ = write!(writer, "{} - ", timestamp);
+            }
+            if self.use_color() {
+                let _ = writeln!(writer,
+                                  "\x1B[{}m{}\x1B[0m - {}",
+                                  level_color(record.level()),
+                                  record.level(),
+                                  record.args());
+            } else {
+                let _ = writeln!(writer, "{} - {}", record.level(), record.args());
             }
-            let _ = writeln!(writer, "{} - {}", record.level(), record.args());
         }
     }
 }
@@ -140,6 +268,10 @@ impl StdErrLog {
             quiet: false,
             timestamp: Timestamp::Off,
             modules: BTreeSet::new(),
+            color: ColorChoice::Never,
+            directives: Vec::new(),
+            target: LogTarget::Stderr,
+            format: None,
             writer: CachedThreadLocal::new(),
         }
     }
@@ -169,11 +301,97 @@ impl StdErrLog {
         self
     }
 
+    /// Selects whether the log-level token of each message is colorized.
+    /// Defaults to `ColorChoice::Never`; call this with `ColorChoice::Auto`
+    /// or `ColorChoice::Always` to opt in.
+    pub fn color(&mut self, choice: ColorChoice) -> &mut StdErrLog {
+        self.color = choice;
+        self
+    }
+
+    /// Configures the destination that log messages are written to
+    pub fn target(&mut self, target: LogTarget) -> &mut StdErrLog {
+        self.target = target;
+        self.writer = CachedThreadLocal::new();
+        self
+    }
+
+    /// Overrides line rendering with a custom callback, taking full
+    /// control over layout (structured output, JSON lines, a PID
+    /// prefix, ...) instead of the built-in `"{level} - {message}"`
+    /// format. Unset by default, in which case the built-in rendering
+    /// (including timestamp and color handling) is unchanged.
+    pub fn format<F>(&mut self, format: F) -> &mut StdErrLog
+        where F: Fn(&mut Write, &log::LogRecord) -> io::Result<()> + Send + Sync + 'static
+    {
+        self.format = Some(Arc::new(format));
+        self
+    }
+
+    /// Parses a `RUST_LOG`-style filter string, setting per-module
+    /// verbosity thresholds.
+    ///
+    /// Each comma-separated entry is either a bare level (e.g. `warn`),
+    /// which sets the global default, or `path::to::mod=level`, which
+    /// sets the threshold for that module and its submodules. Invalid
+    /// entries are ignored.
+    ///
+    /// Directives only raise or lower *how verbose* an already-included
+    /// module is; they do not by themselves decide *whether* a module is
+    /// logged at all. If `module()`/`modules()` have also been used to
+    /// restrict output to a set of modules, a directive for a module
+    /// outside that set has no visible effect - both gates must admit a
+    /// module for it to be logged.
+    pub fn parse_filters(&mut self, filters: &str) -> &mut StdErrLog {
+        for part in filters.split(',') {
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+
+            match part.find('=') {
+                Some(pos) => {
+                    let module = &part[..pos];
+                    if let Ok(level) = part[pos + 1..].parse() {
+                        self.directives.push((module.to_owned(), level));
+                    }
+                }
+                None => {
+                    if let Ok(level) = part.parse() {
+                        self.directives.push((String::new(), level));
+                    }
+                }
+            }
+        }
+
+        self.directives.sort_by(|a, b| a.0.cmp(&b.0));
+        self
+    }
+
+    /// Parses filters from the named environment variable, as with
+    /// `parse_filters`. Has no effect if the variable isn't set.
+    pub fn parse_env(&mut self, env_var: &str) -> &mut StdErrLog {
+        if let Ok(filters) = env::var(env_var) {
+            self.parse_filters(&filters);
+        }
+        self
+    }
+
+    /// Parses filters from the `RUST_LOG` environment variable, as with
+    /// `parse_env`.
+    pub fn parse_default_env(&mut self) -> &mut StdErrLog {
+        self.parse_env("RUST_LOG")
+    }
+
+    /// Restricts logging to the given module (and its submodules). See
+    /// `parse_filters` for how this interacts with per-module directives.
     pub fn module(&mut self, module: &str) -> &mut StdErrLog {
         self.modules.insert(module.to_owned());
         self
     }
 
+    /// Restricts logging to the given modules (and their submodules). See
+    /// `parse_filters` for how this interacts with per-module directives.
     pub fn modules<T: Into<String>, I: IntoIterator<Item = T>>(&mut self,
                                                                modules: I)
                                                                -> &mut StdErrLog {
@@ -181,11 +399,52 @@ impl StdErrLog {
         self
     }
 
-    fn log_level_filter(&self) -> LogLevelFilter {
+    // picks the directive whose module string is the longest prefix of
+    // `module_path`, falling back to the global verbosity if none matches.
+    // because prefixes always sort before the strings they prefix,
+    // scanning the sorted directives in reverse finds the longest match
+    // first, mirroring the `BTreeSet::range` prefix lookup used above.
+    fn level_for_module(&self, module_path: &str) -> LogLevelFilter {
         if self.quiet {
-            LogLevelFilter::Off
-        } else {
-            self.verbosity
+            return LogLevelFilter::Off;
+        }
+
+        self.directives
+            .iter()
+            .rev()
+            .find(|&&(ref module, _)| module_path.starts_with(module.as_str()))
+            .map(|&(_, level)| level)
+            .unwrap_or(self.verbosity)
+    }
+
+    // the `log` crate's global max level must be at least as permissive
+    // as the most verbose directive, or records a specific module wants
+    // would be filtered out before `enabled`/`log` ever see them.
+    fn max_level_filter(&self) -> LogLevelFilter {
+        if self.quiet {
+            return LogLevelFilter::Off;
+        }
+
+        self.directives
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.verbosity, |max, level| if level > max { level } else { max })
+    }
+
+    fn use_color(&self) -> bool {
+        match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                if env::var_os("NO_COLOR").is_some() {
+                    return false;
+                }
+                match self.target {
+                    LogTarget::Stderr => atty::is(atty::Stream::Stderr),
+                    LogTarget::Stdout => atty::is(atty::Stream::Stdout),
+                    LogTarget::Pipe(_) => false,
+                }
+            }
         }
     }
 
@@ -208,7 +467,7 @@ impl StdErrLog {
 
     pub fn init(&self) -> Result<(), log::SetLoggerError> {
         log::set_logger(|max_log_level| {
-                            max_log_level.set(self.log_level_filter());
+                            max_log_level.set(self.max_level_filter());
 
                             Box::new(self.clone())
                         })
@@ -219,6 +478,56 @@ pub fn new() -> StdErrLog {
     StdErrLog::new()
 }
 
+// renders the current time per `timestamp`, or `None` if timestamping
+// is disabled. sub-second variants splice a zero-padded fractional part
+// into the rfc3339 string ahead of its timezone designator.
+fn format_timestamp(timestamp: Timestamp) -> Option<String> {
+    let now = time::now();
+
+    match timestamp {
+        Timestamp::Off => None,
+        Timestamp::Second => Some(now.rfc3339().to_string()),
+        Timestamp::Epoch => Some(now.to_timespec().sec.to_string()),
+        Timestamp::Millisecond | Timestamp::Microsecond | Timestamp::Nanosecond => {
+            Some(splice_fractional(&now.rfc3339().to_string(), now.tm_nsec, timestamp))
+        }
+    }
+}
+
+// splices a zero-padded fractional-second field into an rfc3339
+// timestamp string, ahead of its `Z`/`+hh:mm`/`-hh:mm` timezone
+// designator. the designator is searched for only within the time
+// portion, after the `T`, so a `-` between the year/month/day in the
+// date portion is never mistaken for a negative UTC offset.
+fn splice_fractional(rfc3339: &str, nsec: i32, timestamp: Timestamp) -> String {
+    let time_start = rfc3339.find('T').map(|i| i + 1).unwrap_or(0);
+    let tz_pos = rfc3339[time_start..]
+        .find(&['Z', '+', '-'][..])
+        .map(|i| time_start + i)
+        .unwrap_or(rfc3339.len());
+    let (whole, tz) = rfc3339.split_at(tz_pos);
+
+    let fractional = match timestamp {
+        Timestamp::Millisecond => format!("{:03}", nsec / 1_000_000),
+        Timestamp::Microsecond => format!("{:06}", nsec / 1_000),
+        Timestamp::Nanosecond => format!("{:09}", nsec),
+        _ => unreachable!(),
+    };
+
+    format!("{}.{}{}", whole, fractional, tz)
+}
+
+/// ANSI SGR color code used for a given log level
+fn level_color(level: LogLevel) -> u8 {
+    match level {
+        LogLevel::Error => 31, // red
+        LogLevel::Warn => 33, // yellow
+        LogLevel::Info => 32, // green
+        LogLevel::Debug => 34, // blue
+        LogLevel::Trace => 35, // magenta
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -229,4 +538,49 @@ mod tests {
 
         assert_eq!(log::LogLevel::Error, log::max_log_level())
     }
+
+    #[test]
+    fn test_splice_fractional_negative_offset() {
+        let ts = super::splice_fractional("2026-07-26T09:05:10-04:00",
+                                           216_000_000,
+                                           super::Timestamp::Millisecond);
+
+        assert_eq!("2026-07-26T09:05:10.216-04:00", ts);
+    }
+
+    #[test]
+    fn test_level_for_module_longest_prefix_wins() {
+        extern crate log;
+
+        let mut log = super::new();
+        log.verbosity(0); // Error
+        log.parse_filters("a=warn,a::b=debug");
+
+        assert_eq!(log::LogLevelFilter::Debug, log.level_for_module("a::b::c"));
+        assert_eq!(log::LogLevelFilter::Warn, log.level_for_module("a::x"));
+        assert_eq!(log::LogLevelFilter::Error, log.level_for_module("other"));
+    }
+
+    #[test]
+    fn test_level_for_module_bare_level_is_global_default() {
+        extern crate log;
+
+        let mut log = super::new();
+        log.verbosity(0); // Error
+        log.parse_filters("info,a=warn");
+
+        assert_eq!(log::LogLevelFilter::Info, log.level_for_module("other"));
+        assert_eq!(log::LogLevelFilter::Warn, log.level_for_module("a::b"));
+    }
+
+    #[test]
+    fn test_max_level_filter_picks_most_permissive_directive() {
+        extern crate log;
+
+        let mut log = super::new();
+        log.verbosity(0); // Error
+        log.parse_filters("a=warn,a::b=trace,c=info");
+
+        assert_eq!(log::LogLevelFilter::Trace, log.max_level_filter());
+    }
 }